@@ -0,0 +1,130 @@
+use std::io::{Read, Write};
+
+use cosmwasm_std::{StdError, StdResult};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use secret_toolkit::serialization::{Bincode2, Serde};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Encoded payloads at or above this size are deflated before being written to storage, since
+/// storage is the dominant cost for large values like reminder bodies and history entries.
+pub const COMPRESSION_THRESHOLD: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Bincode,
+    Json,
+    Compressed,
+}
+
+impl Format {
+    fn tag(self) -> u8 {
+        match self {
+            Format::Bincode => 0,
+            Format::Json => 1,
+            Format::Compressed => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> StdResult<Self> {
+        match tag {
+            0 => Ok(Format::Bincode),
+            1 => Ok(Format::Json),
+            2 => Ok(Format::Compressed),
+            other => Err(StdError::parse_err("Format", format!("unknown storage format tag {}", other))),
+        }
+    }
+}
+
+/// Selects the on-disk encoding used for a stored value. `save_with::<C, _, _>` picks the
+/// format when writing; reads are tag-driven and need no `Codec` parameter, so values written
+/// under different codecs can coexist and are migrated lazily as they're read and re-saved.
+///
+/// Reads assume every existing value in storage was already written through `save`/`save_with`,
+/// i.e. this scheme was in place from the contract's first deploy. It does not detect or migrate
+/// values written as plain untagged bincode before codecs existed: the first byte of such a
+/// value isn't reliably distinguishable from a real tag, so there is no safe way to tell the two
+/// apart after the fact.
+pub trait Codec {
+    fn format() -> Format;
+    fn serialize<T: Serialize>(value: &T) -> StdResult<Vec<u8>>;
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> StdResult<T>;
+}
+
+/// The default format, matching the encoding this contract always used before codecs existed.
+pub struct BincodeCodec;
+impl Codec for BincodeCodec {
+    fn format() -> Format { Format::Bincode }
+    fn serialize<T: Serialize>(value: &T) -> StdResult<Vec<u8>> { Bincode2::serialize(value) }
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> StdResult<T> { Bincode2::deserialize(bytes) }
+}
+
+/// Plain JSON, at the cost of a larger encoded size than `BincodeCodec`. Used for small values
+/// worth being able to inspect directly (e.g. with a raw storage query) rather than needing a
+/// bincode decoder on hand.
+pub struct JsonCodec;
+impl Codec for JsonCodec {
+    fn format() -> Format { Format::Json }
+    fn serialize<T: Serialize>(value: &T) -> StdResult<Vec<u8>> { cosmwasm_std::to_vec(value) }
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> StdResult<T> { cosmwasm_std::from_slice(bytes) }
+}
+
+/// Bincode, deflated when the encoded payload is at least [`COMPRESSION_THRESHOLD`] bytes.
+/// Smaller payloads are stored as plain `Bincode` since the deflate header would cost more
+/// than it saves.
+pub struct CompressedCodec;
+impl Codec for CompressedCodec {
+    fn format() -> Format { Format::Compressed }
+    fn serialize<T: Serialize>(value: &T) -> StdResult<Vec<u8>> { Bincode2::serialize(value) }
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> StdResult<T> { Bincode2::deserialize(bytes) }
+}
+
+pub fn encode<T: Serialize, C: Codec>(value: &T) -> StdResult<Vec<u8>> {
+    let (tag, body) = match C::format() {
+        Format::Compressed => {
+            let raw = C::serialize(value)?;
+            if raw.len() >= COMPRESSION_THRESHOLD {
+                (Format::Compressed.tag(), deflate(&raw)?)
+            } else {
+                (Format::Bincode.tag(), raw)
+            }
+        }
+        format => (format.tag(), C::serialize(value)?),
+    };
+
+    let mut out = Vec::with_capacity(body.len() + 1);
+    out.push(tag);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> StdResult<T> {
+    let (tag, body) = bytes
+        .split_first()
+        .ok_or_else(|| StdError::generic_err("Stored value is missing its format tag."))?;
+
+    match Format::from_tag(*tag)? {
+        Format::Bincode => BincodeCodec::deserialize(body),
+        Format::Json => JsonCodec::deserialize(body),
+        Format::Compressed => BincodeCodec::deserialize(&inflate(body)?),
+    }
+}
+
+fn deflate(bytes: &[u8]) -> StdResult<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(bytes)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+    encoder.finish().map_err(|e| StdError::generic_err(e.to_string()))
+}
+
+fn inflate(bytes: &[u8]) -> StdResult<Vec<u8>> {
+    let mut decoder = DeflateDecoder::new(bytes);
+    let mut out = Vec::new();
+    decoder
+        .read_to_end(&mut out)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+    Ok(out)
+}
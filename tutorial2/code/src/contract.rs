@@ -3,14 +3,20 @@ use cosmwasm_std::{
     StdResult, Storage, QueryResult, HumanAddr,
 };
 use std::convert::TryFrom;
-use crate::msg::{HandleMsg, InitMsg, QueryMsg, HandleAnswer, QueryAnswer,};
-use crate::state::{load, may_load, save, State, Reminder, CONFIG_KEY, write_viewing_key, read_viewing_key,};
+use crate::msg::{
+    HandleMsg, InitMsg, QueryMsg, HandleAnswer, QueryAnswer, HistoryEntry, Permission, Permit,
+    QueryWithPermit, SharedReminderEntry,
+};
+use crate::permit::validate_permit;
+use crate::repo::{ReadonlyStorageRepo, ReminderRepo, StorageRepo};
+use crate::state::{State, SharedReminder, WrappedKeyEntry};
 use crate::viewing_key::{ViewingKey, VIEWING_KEY_SIZE};
+use crate::crypto::{encrypt, decrypt, wrap_key, derive_wrapping_key, KEY_SIZE};
 use secret_toolkit::crypto::sha_256;
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    _env: Env,
+    env: Env,
     msg: InitMsg,
 ) -> StdResult<InitResponse> {
     let max_size = match valid_max_size(msg.max_size) {
@@ -22,9 +28,12 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         max_size,
         reminder_count: 0_u64,
         prng_seed: sha_256(base64::encode(msg.prng_seed).as_bytes()).to_vec(),
+        admin: deps.api.canonical_address(&env.message.sender)?,
+        paused: false,
+        contract: deps.api.canonical_address(&env.contract.address)?,
     };
 
-    save(&mut deps.storage, CONFIG_KEY, &config)?;
+    StorageRepo::new(&mut deps.storage).put_config(&config)?;
     Ok(InitResponse::default())
 }
 
@@ -42,15 +51,20 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
+    let mut repo = StorageRepo::new(&mut deps.storage);
     match msg {
-        HandleMsg::Record { reminder } => try_record(deps, env, reminder),
-        HandleMsg::Read { } => try_read(deps, env),
-        HandleMsg::GenerateViewingKey { entropy, .. } => try_generate_viewing_key(deps, env, entropy),
+        HandleMsg::Record { reminder } => try_record(&mut repo, &deps.api, env, reminder),
+        HandleMsg::Read { } => try_read(&mut repo, &deps.api, env),
+        HandleMsg::GenerateViewingKey { entropy, .. } => try_generate_viewing_key(&mut repo, &deps.api, env, entropy),
+        HandleMsg::Share { reminder, recipients, entropy } => try_share(&mut repo, &deps.api, env, reminder, recipients, entropy),
+        HandleMsg::UpdateConfig { max_size, admin, paused } => try_update_config(&mut repo, &deps.api, env, max_size, admin, paused),
+        HandleMsg::RevokePermit { name } => try_revoke_permit(&mut repo, &deps.api, env, name),
     }
 }
 
-fn try_record<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
+fn try_record<A: Api>(
+    repo: &mut dyn ReminderRepo,
+    api: &A,
     env: Env,
     reminder: String,
 ) -> StdResult<HandleResponse> {
@@ -58,27 +72,26 @@ fn try_record<S: Storage, A: Api, Q: Querier>(
     let reminder = reminder.as_bytes();
 
     // retrieve the config state from storage
-    let mut config: State = load(&mut deps.storage, CONFIG_KEY)?;
+    let mut config = repo.get_config()?;
 
-    if reminder.len() > config.max_size.into() {
+    if config.paused {
+        // contract is paused; report it through the status field like every other
+        // user-facing outcome here, rather than aborting the tx
+        status = String::from("Contract is paused. Reminder not recorded.");
+    } else if reminder.len() > config.max_size.into() {
         // if reminder content is too long, set status message and do nothing else
         status = String::from("Message is too long. Reminder not recorded.");
     } else {
         // get the canonical address of sender
-        let sender_address = deps.api.canonical_address(&env.message.sender)?;
+        let sender_address = api.canonical_address(&env.message.sender)?;
 
-        // create the reminder struct containing content string and timestamp
-        let stored_reminder = Reminder {
-            content: reminder.to_vec(),
-            timestamp: env.block.time
-        };
-
-        // save the reminder using a byte vector representation of the sender's address as the key
-        save(&mut deps.storage, &sender_address.as_slice().to_vec(), &stored_reminder)?;
+        // append this edit to the sender's operation log rather than overwriting the
+        // previous reminder, so old versions remain readable through `History`
+        repo.put_reminder(&sender_address, reminder.to_vec(), env.block.time)?;
 
         // increment the reminder_count
         config.reminder_count += 1;
-        save(&mut deps.storage, CONFIG_KEY, &config)?;
+        repo.put_config(&config)?;
 
         // set the status message
         status = String::from("Reminder recorded!");
@@ -94,28 +107,37 @@ fn try_record<S: Storage, A: Api, Q: Querier>(
     })
 }
 
-fn try_read<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
+fn try_read<A: Api>(
+    repo: &mut dyn ReminderRepo,
+    api: &A,
     env: Env,
 ) -> StdResult<HandleResponse> {
+    let config = repo.get_config()?;
+
     let status: String;
     let mut reminder: Option<String> = None;
     let mut timestamp: Option<u64> = None;
 
-    let sender_address = deps.api.canonical_address(&env.message.sender)?;
-
-    // read the reminder from storage
-    let result: Option<Reminder> = may_load(&mut deps.storage, &sender_address.as_slice().to_vec()).ok().unwrap();
-    match result {
-        // set all response field values
-        Some(stored_reminder) => {
-            status = String::from("Reminder found.");
-            reminder = String::from_utf8(stored_reminder.content).ok();
-            timestamp = Some(stored_reminder.timestamp);
-        }
-        // unless there's an error
-        None => { status = String::from("Reminder not found."); }
-    };
+    if config.paused {
+        // contract is paused; report it through the status field like every other
+        // user-facing outcome here, rather than aborting the tx
+        status = String::from("Contract is paused.");
+    } else {
+        let sender_address = api.canonical_address(&env.message.sender)?;
+
+        // reconstruct the current reminder from the latest checkpoint plus any ops after it
+        let result = repo.get_reminder(&sender_address)?;
+        match result {
+            // set all response field values
+            Some(stored_reminder) => {
+                status = String::from("Reminder found.");
+                reminder = String::from_utf8(stored_reminder.content).ok();
+                timestamp = Some(stored_reminder.timestamp);
+            }
+            // unless there's an error
+            None => { status = String::from("Reminder not found."); }
+        };
+    }
 
     // Return a HandleResponse with status message, reminder, and timestamp included in the data field
     Ok(HandleResponse {
@@ -129,49 +151,201 @@ fn try_read<S: Storage, A: Api, Q: Querier>(
     })
 }
 
-pub fn try_generate_viewing_key<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
+pub fn try_generate_viewing_key<A: Api>(
+    repo: &mut dyn ReminderRepo,
+    api: &A,
     env: Env,
     entropy: String,
 ) -> StdResult<HandleResponse> {
-    let config: State = load(&mut deps.storage, CONFIG_KEY)?;
+    let config = repo.get_config()?;
     let prng_seed = config.prng_seed;
 
     let key = ViewingKey::new(&env, &prng_seed, (&entropy).as_ref());
 
-    let message_sender = deps.api.canonical_address(&env.message.sender)?;
+    let message_sender = api.canonical_address(&env.message.sender)?;
 
-    write_viewing_key(&mut deps.storage, &message_sender, &key);
+    repo.put_viewing_key(&message_sender, &key);
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::GenerateViewingKey { 
+        data: Some(to_binary(&HandleAnswer::GenerateViewingKey {
             key,
         })?),
     })
 }
 
+fn try_share<A: Api>(
+    repo: &mut dyn ReminderRepo,
+    api: &A,
+    env: Env,
+    reminder: String,
+    recipients: Vec<HumanAddr>,
+    entropy: String,
+) -> StdResult<HandleResponse> {
+    let config = repo.get_config()?;
+    let sender_address = api.canonical_address(&env.message.sender)?;
+
+    // derive a one-time content key and nonce for this share; the body is encrypted with it
+    // once and stored once, no matter how many recipients it is shared with
+    let mut key_material = config.prng_seed.clone();
+    key_material.extend_from_slice(sender_address.as_slice());
+    key_material.extend_from_slice(entropy.as_bytes());
+    key_material.extend_from_slice(&env.block.time.to_be_bytes());
+    let content_key: [u8; KEY_SIZE] = sha_256(&key_material);
+    let share_id = sha_256(&[content_key.as_slice(), &b"id"[..]].concat()).to_vec();
+    let nonce = sha_256(&[content_key.as_slice(), &b"nonce"[..]].concat())[..12].to_vec();
+
+    let ciphertext = encrypt(&content_key, &nonce, reminder.as_bytes());
+    repo.put_shared(&share_id, &SharedReminder {
+        author: sender_address,
+        ciphertext,
+        nonce,
+        timestamp: env.block.time,
+    })?;
+
+    let mut status = String::from("Reminder shared!");
+    for recipient in recipients {
+        let recipient_address = api.canonical_address(&recipient)?;
+
+        match repo.get_viewing_key(&recipient_address) {
+            Some(hashed_viewing_key) => {
+                let wrapping_key = derive_wrapping_key(&hashed_viewing_key, recipient_address.as_slice());
+                repo.put_wrapped_key(&recipient_address, &WrappedKeyEntry {
+                    share_id: share_id.clone(),
+                    wrapped_key: wrap_key(&wrapping_key, &content_key),
+                    vk_hash: hashed_viewing_key,
+                })?;
+            }
+            // a recipient without a viewing key has no key material to wrap the content key
+            // with, so they are skipped (and the status says so) rather than failing the whole share
+            None => status = String::from("Reminder shared, but some recipients have no viewing key yet."),
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Share { status })?),
+    })
+}
+
+fn try_update_config<A: Api>(
+    repo: &mut dyn ReminderRepo,
+    api: &A,
+    env: Env,
+    max_size: Option<i32>,
+    admin: Option<HumanAddr>,
+    paused: Option<bool>,
+) -> StdResult<HandleResponse> {
+    let mut config = repo.get_config()?;
+
+    let sender_address = api.canonical_address(&env.message.sender)?;
+    if sender_address != config.admin {
+        return Err(StdError::unauthorized());
+    }
+
+    if let Some(max_size) = max_size {
+        config.max_size = match valid_max_size(max_size) {
+            Some(v) => v,
+            None => return Err(StdError::generic_err("Invalid max_size. Must be in the range of 1..65535.")),
+        };
+    }
+    if let Some(admin) = admin {
+        config.admin = api.canonical_address(&admin)?;
+    }
+    if let Some(paused) = paused {
+        config.paused = paused;
+    }
+
+    repo.put_config(&config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::UpdateConfig {
+            status: String::from("Config updated!"),
+        })?),
+    })
+}
+
+fn try_revoke_permit<A: Api>(
+    repo: &mut dyn ReminderRepo,
+    api: &A,
+    env: Env,
+    name: String,
+) -> StdResult<HandleResponse> {
+    let sender_address = api.canonical_address(&env.message.sender)?;
+    repo.revoke_permit(&sender_address, &name)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokePermit {
+            status: String::from("Permit revoked!"),
+        })?),
+    })
+}
+
 pub fn query<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     msg: QueryMsg,
 ) -> StdResult<Binary> {
+    let repo = ReadonlyStorageRepo::new(&deps.storage);
     match msg {
-        QueryMsg::Stats { } => query_stats(deps),
-        _ => authenticated_queries(deps, msg),
+        QueryMsg::Stats { } => query_stats(&repo, &deps.api),
+        QueryMsg::WithPermit { permit, query } => query_with_permit(&repo, &deps.api, permit, query),
+        _ => authenticated_queries(&repo, &deps.api, msg),
     }
 }
 
-fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
+/// Authenticates with a [`Permit`] instead of a viewing key, so `Read` and `History` can be
+/// authorized purely offline: no `GenerateViewingKey` transaction is required first, and there
+/// is no key-existence timing side channel to guard against since the signature itself proves
+/// who is asking. `ReadShared` is the exception: unwrapping a shared reminder's content key
+/// fundamentally needs the recipient's *hashed viewing key* as key material (see
+/// `derive_wrapping_key`), which a permit carries no equivalent of, so a permit-authenticated
+/// `ReadShared` still requires the caller to have called `GenerateViewingKey` beforehand, or it
+/// fails the same way an unauthenticated request would.
+fn query_with_permit<A: Api>(
+    repo: &dyn ReminderRepo,
+    api: &A,
+    permit: Permit,
+    query: QueryWithPermit,
+) -> QueryResult {
+    let required = match &query {
+        QueryWithPermit::Read { } => Permission::Read,
+        QueryWithPermit::History { .. } => Permission::History,
+        QueryWithPermit::ReadShared { } => Permission::ReadShared,
+    };
+
+    let config = repo.get_config()?;
+    validate_permit(api, &permit, required, &config.contract)?;
+
+    let owner_address = api.canonical_address(&permit.params.owner)?;
+    if repo.is_permit_revoked(&owner_address, &permit.params.permit_name) {
+        return Err(StdError::unauthorized());
+    }
+
+    match query {
+        QueryWithPermit::Read { } => query_read(repo, api, &permit.params.owner),
+        QueryWithPermit::History { page, page_size } =>
+            query_history(repo, api, &permit.params.owner, page, page_size),
+        QueryWithPermit::ReadShared { } => query_read_shared(repo, api, &permit.params.owner),
+    }
+}
+
+fn authenticated_queries<A: Api>(
+    repo: &dyn ReminderRepo,
+    api: &A,
     msg: QueryMsg,
 ) -> QueryResult {
     let (addresses, key) = msg.get_validation_params();
 
     for address in addresses {
-        let canonical_addr = deps.api.canonical_address(address)?;
+        let canonical_addr = api.canonical_address(address)?;
 
-        let expected_key = read_viewing_key(&deps.storage, &canonical_addr);
+        let expected_key = repo.get_viewing_key(&canonical_addr);
 
         if expected_key.is_none() {
             // Checking the key will take significant time. We don't want to exit immediately if it isn't set
@@ -181,7 +355,11 @@ fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
 
             return match msg {
                 QueryMsg::Read { address, .. } =>
-                    query_read(&deps, &address),
+                    query_read(repo, api, &address),
+                QueryMsg::History { address, page, page_size, .. } =>
+                    query_history(repo, api, &address, page, page_size),
+                QueryMsg::ReadShared { address, .. } =>
+                    query_read_shared(repo, api, &address),
                 _ => panic!("This query type does not require authentication"),
             };
         }
@@ -190,18 +368,19 @@ fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
     Err(StdError::unauthorized())
 }
 
-fn query_read<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
+fn query_read<A: Api>(
+    repo: &dyn ReminderRepo,
+    api: &A,
     address: &HumanAddr,
 ) -> StdResult<Binary> {
     let status: String;
     let mut reminder: Option<String> = None;
     let mut timestamp: Option<u64> = None;
 
-    let sender_address = deps.api.canonical_address(&address)?;
+    let sender_address = api.canonical_address(&address)?;
 
-    // read the reminder from storage
-    let result: Option<Reminder> = may_load(&deps.storage, &sender_address.as_slice().to_vec()).ok().unwrap();
+    // reconstruct the current reminder from the latest checkpoint plus any ops after it
+    let result = repo.get_reminder(&sender_address)?;
     match result {
         // set all response field values
         Some(stored_reminder) => {
@@ -216,10 +395,111 @@ fn query_read<S: Storage, A: Api, Q: Querier>(
     to_binary(&QueryAnswer::Read{ status, reminder, timestamp })
 }
 
-fn query_stats<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
-    // retrieve the config state from storage
-    let config: State = load(&deps.storage, CONFIG_KEY)?;
-    to_binary(&QueryAnswer::Stats{ reminder_count: config.reminder_count })
+fn query_history<A: Api>(
+    repo: &dyn ReminderRepo,
+    api: &A,
+    address: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let sender_address = api.canonical_address(&address)?;
+
+    let entries = repo.get_history(&sender_address, page, page_size)?
+        .into_iter()
+        .map(|op| HistoryEntry {
+            seq: op.seq,
+            reminder: String::from_utf8(op.content).ok(),
+            timestamp: op.timestamp,
+        })
+        .collect();
+
+    to_binary(&QueryAnswer::History { entries })
 }
 
+/// Returns every reminder shared with `address` that can still be unwrapped with its *current*
+/// viewing key. An entry wrapped under an earlier, now-rotated viewing key (see
+/// [`derive_wrapping_key`]) or whose ciphertext doesn't authenticate (see [`decrypt`]) is
+/// dropped rather than surfaced, since there is no content we can safely show for it.
+fn query_read_shared<A: Api>(
+    repo: &dyn ReminderRepo,
+    api: &A,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let recipient_address = api.canonical_address(address)?;
+
+    let hashed_viewing_key = match repo.get_viewing_key(&recipient_address) {
+        Some(key) => key,
+        // nothing can be unwrapped without a current viewing key, regardless of how the
+        // caller authenticated (see the permit doc comment on `query_with_permit`)
+        None => return Err(StdError::unauthorized()),
+    };
+
+    let wrapped_entries = repo.get_wrapped_keys(&recipient_address)?;
+    let mut stale = false;
+    let mut shares = Vec::new();
 
+    for entry in wrapped_entries {
+        if entry.vk_hash != hashed_viewing_key {
+            // wrapped under a viewing key that's since been rotated; the content key can no
+            // longer be recovered, so skip it instead of deriving the wrong wrapping key
+            stale = true;
+            continue;
+        }
+
+        let body = match repo.get_shared(&entry.share_id)? {
+            Some(body) => body,
+            None => continue,
+        };
+
+        let wrapping_key = derive_wrapping_key(&hashed_viewing_key, recipient_address.as_slice());
+        let content_key = wrap_key(&wrapping_key, &array_ref(&entry.wrapped_key)?);
+        let plaintext = match decrypt(&content_key, &body.nonce, &body.ciphertext) {
+            Some(plaintext) => plaintext,
+            // fails to authenticate; never surface it as a successful "found" result
+            None => continue,
+        };
+        let reminder = match String::from_utf8(plaintext) {
+            Ok(reminder) => reminder,
+            Err(_) => continue,
+        };
+
+        shares.push(SharedReminderEntry {
+            reminder,
+            author: api.human_address(&body.author)?,
+            timestamp: body.timestamp,
+        });
+    }
+
+    let status = if !shares.is_empty() {
+        String::from("Shared reminder found.")
+    } else if stale {
+        String::from("Shared reminder not found: viewing key has changed since it was shared.")
+    } else {
+        String::from("Shared reminder not found.")
+    };
+
+    to_binary(&QueryAnswer::ReadShared { status, shares })
+}
+
+/// Converts a `Vec<u8>` of the expected key length back into a fixed-size array so it can be
+/// unwrapped with [`wrap_key`].
+fn array_ref(bytes: &[u8]) -> StdResult<[u8; KEY_SIZE]> {
+    if bytes.len() != KEY_SIZE {
+        return Err(StdError::generic_err("Corrupt wrapped key."));
+    }
+    let mut out = [0u8; KEY_SIZE];
+    out.copy_from_slice(bytes);
+    Ok(out)
+}
+
+fn query_stats<A: Api>(repo: &dyn ReminderRepo, api: &A) -> StdResult<Binary> {
+    // retrieve the config state from storage
+    let config = repo.get_config()?;
+    let admin = api.human_address(&config.admin)?;
+    to_binary(&QueryAnswer::Stats {
+        reminder_count: config.reminder_count,
+        max_size: config.max_size,
+        admin,
+        paused: config.paused,
+    })
+}
@@ -0,0 +1,96 @@
+use secret_toolkit::crypto::sha_256;
+
+/// Size in bytes of a content key or a recipient's derived wrapping key.
+pub const KEY_SIZE: usize = 32;
+
+/// Size in bytes of the authentication tag [`encrypt`] appends to every ciphertext.
+pub const TAG_SIZE: usize = 32;
+
+/// Expands `key`/`nonce` into a keystream of `len` bytes by hashing an incrementing counter
+/// alongside them, then XORs it with `data`. Calling this twice with the same key/nonce
+/// encrypts and decrypts, since XOR is its own inverse.
+fn xor_with_keystream(key: &[u8], nonce: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut counter: u32 = 0;
+
+    while out.len() < data.len() {
+        let mut block_input = key.to_vec();
+        block_input.extend_from_slice(nonce);
+        block_input.extend_from_slice(&counter.to_be_bytes());
+        out.extend_from_slice(&sha_256(&block_input));
+        counter += 1;
+    }
+
+    out.truncate(data.len());
+    out.iter().zip(data).map(|(k, d)| k ^ d).collect()
+}
+
+/// Computes an authentication tag over `ciphertext`, bound to `key` and `nonce`. Double-hashes
+/// with `key` mixed in on both ends, rather than a single `sha_256(key || ciphertext)`, so the
+/// tag can't be forged by length-extending the inner hash without knowing `key`.
+fn compute_tag(key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> [u8; TAG_SIZE] {
+    let mut inner = key.to_vec();
+    inner.extend_from_slice(nonce);
+    inner.extend_from_slice(ciphertext);
+    let inner_hash = sha_256(&inner);
+
+    let mut outer = key.to_vec();
+    outer.extend_from_slice(&inner_hash);
+    sha_256(&outer)
+}
+
+/// Compares two byte slices without branching on where they first differ, so checking a
+/// supplied tag against the expected one can't leak timing information about how many bytes
+/// matched.
+fn ct_slice_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
+
+/// Encrypts `plaintext` with `key`/`nonce` and appends an authentication tag. Without a tag, a
+/// wrong key (e.g. a stale one) or tampered ciphertext would silently decrypt to garbage rather
+/// than being detected, since a plain XOR stream cipher has no integrity of its own.
+pub fn encrypt(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut sealed = xor_with_keystream(key, nonce, plaintext);
+    let tag = compute_tag(key, nonce, &sealed);
+    sealed.extend_from_slice(&tag);
+    sealed
+}
+
+/// Decrypts `sealed` (as produced by `encrypt`) with `key`/`nonce`, verifying its authentication
+/// tag first. Returns `None` instead of garbage plaintext if the tag doesn't match, e.g. because
+/// `key` is wrong or `sealed` was tampered with; callers must treat that as a failure, not as an
+/// empty-but-valid result.
+pub fn decrypt(key: &[u8], nonce: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < TAG_SIZE {
+        return None;
+    }
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_SIZE);
+    if ct_slice_eq(&compute_tag(key, nonce, ciphertext), tag) {
+        Some(xor_with_keystream(key, nonce, ciphertext))
+    } else {
+        None
+    }
+}
+
+/// Wraps (or unwraps) a content key so that only someone who can reproduce `wrapping_key` can
+/// recover it. `wrapping_key` is expected to be `KEY_SIZE` bytes, e.g. derived from
+/// [`derive_wrapping_key`].
+pub fn wrap_key(wrapping_key: &[u8], content_key: &[u8; KEY_SIZE]) -> Vec<u8> {
+    content_key.iter().zip(wrapping_key).map(|(c, w)| c ^ w).collect()
+}
+
+/// Derives a per-recipient wrapping key from the recipient's hashed viewing key and their
+/// address. Only the recipient (who knows the plaintext viewing key and can hash it the same
+/// way the contract does) and the contract itself can compute this.
+pub fn derive_wrapping_key(hashed_viewing_key: &[u8], recipient: &[u8]) -> [u8; KEY_SIZE] {
+    let mut material = hashed_viewing_key.to_vec();
+    material.extend_from_slice(recipient);
+    sha_256(&material)
+}
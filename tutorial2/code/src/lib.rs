@@ -0,0 +1,10 @@
+pub mod codec;
+pub mod contract;
+pub mod crypto;
+pub mod msg;
+pub mod permit;
+pub mod repo;
+pub mod state;
+pub mod viewing_key;
+
+pub use crate::contract::{handle, init, query};
@@ -0,0 +1,226 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use cosmwasm_std::{Binary, HumanAddr};
+
+use crate::viewing_key::ViewingKey;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InitMsg {
+    /// Maximum size of a reminder message in bytes
+    pub max_size: i32,
+    /// Entropy used to seed the contract's prng, which derives viewing keys
+    pub prng_seed: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    /// Records a new reminder for the sender
+    Record {
+        reminder: String,
+    },
+    /// Requests the current reminder for the sender
+    Read { },
+    /// Generates a new viewing key for the sender, used to authenticate queries
+    GenerateViewingKey {
+        entropy: String,
+        padding: Option<String>,
+    },
+    /// Shares a reminder with a set of recipients without storing its plaintext per recipient.
+    /// Each recipient must already have a viewing key set, since it is used to derive the key
+    /// that wraps the content key for them.
+    Share {
+        reminder: String,
+        recipients: Vec<HumanAddr>,
+        entropy: String,
+    },
+    /// Updates live-reloadable contract settings. Only the current admin may call this, and
+    /// any field left as `None` is left unchanged.
+    UpdateConfig {
+        max_size: Option<i32>,
+        admin: Option<HumanAddr>,
+        paused: Option<bool>,
+    },
+    /// Invalidates a previously issued permit by name, so a leaked permit can no longer be
+    /// used to authenticate queries as the sender.
+    RevokePermit {
+        name: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// Gets basic statistics about the use of the contract
+    Stats { },
+    /// Gets the current reminder for an address, requires a viewing key
+    Read {
+        address: HumanAddr,
+        key: String,
+    },
+    /// Gets a page of past reminder versions for an address, requires a viewing key
+    History {
+        address: HumanAddr,
+        key: String,
+        page: u32,
+        page_size: u32,
+    },
+    /// Gets every reminder shared with an address, requires that address's viewing key
+    ReadShared {
+        address: HumanAddr,
+        key: String,
+    },
+    /// Authenticates with an offline-signed permit instead of a viewing key. The address being
+    /// queried is the permit's own `params.owner`, not a separate field, since the signature
+    /// already proves who is asking.
+    WithPermit {
+        permit: Permit,
+        query: QueryWithPermit,
+    },
+}
+
+/// The subset of `QueryMsg` that can be authenticated with a [`Permit`] rather than a viewing
+/// key. Unlike their `QueryMsg` counterparts, these variants carry no `address`/`key` fields,
+/// since the permit's signature already identifies and authenticates the caller.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    Read { },
+    History {
+        page: u32,
+        page_size: u32,
+    },
+    /// Still requires the caller to have a viewing key set via `HandleMsg::GenerateViewingKey`,
+    /// since unwrapping a shared content key needs that key's hash as material; the permit only
+    /// replaces the *authentication* step, not the key-wrapping scheme itself.
+    ReadShared { },
+}
+
+/// A permission a [`Permit`] can grant, checked against the [`QueryWithPermit`] variant being
+/// run before the permit is accepted.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Read,
+    History,
+    ReadShared,
+}
+
+/// The parameters an owner signs off-chain to authorize queries on their behalf, without
+/// requiring an on-chain `GenerateViewingKey` transaction first.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PermitParams {
+    /// Name of this permit, so the owner can revoke it independently of any others they've
+    /// issued, via `HandleMsg::RevokePermit`.
+    pub permit_name: String,
+    pub allowed_permissions: Vec<Permission>,
+    pub owner: HumanAddr,
+    /// The contract this permit was signed for, checked against the executing contract's own
+    /// address (recorded in `State` at `init`, since `query` isn't passed an `Env` to read it
+    /// from directly) so a permit can't be replayed against a different contract.
+    pub contract: HumanAddr,
+}
+
+/// A recoverable secp256k1 signature over the canonical encoding of [`PermitParams`]. The
+/// public key is intentionally not included here; it is recovered from the signature itself,
+/// so a forged `owner` field cannot be used to borrow someone else's permissions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PermitSignature {
+    pub signature: Binary,
+    pub recovery_id: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Permit {
+    pub params: PermitParams,
+    pub signature: PermitSignature,
+}
+
+impl QueryMsg {
+    /// Returns the addresses that must be checked against the supplied viewing key before
+    /// this query is allowed to run.
+    pub fn get_validation_params(&self) -> (Vec<&HumanAddr>, ViewingKey) {
+        match self {
+            Self::Read { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::History { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::ReadShared { address, key } => (vec![address], ViewingKey(key.clone())),
+            _ => panic!("This query type does not require authentication"),
+        }
+    }
+}
+
+/// Responses from handle functions
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleAnswer {
+    /// Return a status message to let the user know if it succeeded or failed
+    Record {
+        status: String,
+    },
+    /// Return a status message and the current reminder and its timestamp, if it exists
+    Read {
+        status: String,
+        reminder: Option<String>,
+        timestamp: Option<u64>,
+    },
+    /// Return the newly generated viewing key
+    GenerateViewingKey {
+        key: ViewingKey,
+    },
+    /// Return a status message after attempting to share a reminder
+    Share {
+        status: String,
+    },
+    /// Return a status message after attempting to update the contract config
+    UpdateConfig {
+        status: String,
+    },
+    /// Return a status message after revoking a permit
+    RevokePermit {
+        status: String,
+    },
+}
+
+/// A single past version of a reminder, as recorded in its operation history
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct HistoryEntry {
+    pub seq: u64,
+    pub reminder: Option<String>,
+    pub timestamp: u64,
+}
+
+/// One reminder shared with the queried address, as returned by `QueryAnswer::ReadShared`.
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct SharedReminderEntry {
+    pub reminder: String,
+    pub author: HumanAddr,
+    pub timestamp: u64,
+}
+
+/// Responses from query functions
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryAnswer {
+    /// Return basic statistics about contract, including the currently applied config
+    Stats {
+        reminder_count: u64,
+        max_size: u16,
+        admin: HumanAddr,
+        paused: bool,
+    },
+    /// Return the current reminder for an address
+    Read {
+        status: String,
+        reminder: Option<String>,
+        timestamp: Option<u64>,
+    },
+    /// Return a page of past reminder versions for an address, most recent first
+    History {
+        entries: Vec<HistoryEntry>,
+    },
+    /// Return every shared reminder an address has received, oldest first
+    ReadShared {
+        status: String,
+        shares: Vec<SharedReminderEntry>,
+    },
+}
@@ -0,0 +1,58 @@
+use cosmwasm_std::{Api, Binary, CanonicalAddr, StdError, StdResult};
+use ripemd160::{Digest as _, Ripemd160};
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, Secp256k1};
+use sha2::{Digest as _, Sha256};
+
+use crate::msg::{Permission, Permit};
+
+/// Verifies that `permit` is validly signed by its claimed owner, scoped to `contract`, and
+/// grants `required`. Recovers the signer's public key from the signature rather than requiring
+/// the caller to supply it, so a forged `owner` field can't be used to impersonate someone else.
+pub fn validate_permit<A: Api>(
+    api: &A,
+    permit: &Permit,
+    required: Permission,
+    contract: &CanonicalAddr,
+) -> StdResult<()> {
+    if !permit.params.allowed_permissions.contains(&required) {
+        return Err(StdError::unauthorized());
+    }
+
+    // binds the permit to the contract it was signed for, so one signed for this contract
+    // can't be replayed against a different contract running the same scheme
+    if &api.canonical_address(&permit.params.contract)? != contract {
+        return Err(StdError::unauthorized());
+    }
+
+    let signed_bytes = cosmwasm_std::to_vec(&permit.params)?;
+    let message = Message::from_slice(&Sha256::digest(&signed_bytes))
+        .map_err(|_| StdError::generic_err("Could not hash permit payload."))?;
+
+    let recovery_id = RecoveryId::from_i32(permit.signature.recovery_id as i32)
+        .map_err(|_| StdError::generic_err("Invalid permit recovery id."))?;
+    let recoverable_signature =
+        RecoverableSignature::from_compact(permit.signature.signature.as_slice(), recovery_id)
+            .map_err(|_| StdError::generic_err("Invalid permit signature."))?;
+
+    let secp = Secp256k1::verification_only();
+    let pubkey = secp
+        .recover_ecdsa(&message, &recoverable_signature)
+        .map_err(|_| StdError::unauthorized())?;
+
+    let signer = pubkey_to_canonical(&pubkey.serialize());
+    let owner = api.canonical_address(&permit.params.owner)?;
+    if signer != owner {
+        return Err(StdError::unauthorized());
+    }
+
+    Ok(())
+}
+
+/// Derives the cosmos-style account address (ripemd160 of the sha256 of the compressed
+/// pubkey) directly as a `CanonicalAddr`, without needing to round-trip through bech32.
+fn pubkey_to_canonical(pubkey: &[u8]) -> CanonicalAddr {
+    let sha_hash = Sha256::digest(pubkey);
+    let ripemd_hash = Ripemd160::digest(&sha_hash);
+    CanonicalAddr(Binary(ripemd_hash.to_vec()))
+}
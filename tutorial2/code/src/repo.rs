@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use cosmwasm_std::{CanonicalAddr, ReadonlyStorage, StdError, StdResult, Storage};
+
+use crate::state::{
+    append_op, current_reminder, get_shared_body, get_wrapped_keys, is_permit_revoked,
+    list_history, load, put_shared_body, put_wrapped_key, read_viewing_key, revoke_permit, save,
+    write_viewing_key, Op, Reminder, SharedReminder, State, WrappedKeyEntry, CONFIG_KEY,
+};
+use crate::viewing_key::ViewingKey;
+
+/// Persistence surface the contract's handle/query logic runs against, so it can be driven by
+/// real CosmWasm storage in production or a plain in-memory map in unit tests and fuzz targets,
+/// without pulling in a full CosmWasm environment for the latter.
+pub trait ReminderRepo {
+    fn get_config(&self) -> StdResult<State>;
+    fn put_config(&mut self, config: &State) -> StdResult<()>;
+
+    /// Returns the current reminder for `addr`, reconstructed from whatever op log/checkpoint
+    /// scheme the implementation uses.
+    fn get_reminder(&self, addr: &CanonicalAddr) -> StdResult<Option<Reminder>>;
+    /// Appends a new reminder edit for `addr` and returns its sequence number.
+    fn put_reminder(&mut self, addr: &CanonicalAddr, content: Vec<u8>, timestamp: u64) -> StdResult<u64>;
+    /// Returns one page of `addr`'s reminder history, most recent edit first.
+    fn get_history(&self, addr: &CanonicalAddr, page: u32, page_size: u32) -> StdResult<Vec<Op>>;
+
+    fn get_viewing_key(&self, addr: &CanonicalAddr) -> Option<Vec<u8>>;
+    fn put_viewing_key(&mut self, addr: &CanonicalAddr, key: &ViewingKey);
+
+    fn get_shared(&self, share_id: &[u8]) -> StdResult<Option<SharedReminder>>;
+    fn put_shared(&mut self, share_id: &[u8], body: &SharedReminder) -> StdResult<()>;
+    /// Returns every wrapped key entry `recipient` has received, in the order they were shared.
+    fn get_wrapped_keys(&self, recipient: &CanonicalAddr) -> StdResult<Vec<WrappedKeyEntry>>;
+    fn put_wrapped_key(&mut self, recipient: &CanonicalAddr, entry: &WrappedKeyEntry) -> StdResult<()>;
+
+    /// Returns whether `owner` has revoked the permit named `name` via `HandleMsg::RevokePermit`.
+    fn is_permit_revoked(&self, owner: &CanonicalAddr, name: &str) -> bool;
+    fn revoke_permit(&mut self, owner: &CanonicalAddr, name: &str) -> StdResult<()>;
+}
+
+/// The production `ReminderRepo`, backed by the contract's real `PrefixedStorage` layout as
+/// defined in the `state` module.
+pub struct StorageRepo<'a, S: Storage> {
+    storage: &'a mut S,
+}
+
+impl<'a, S: Storage> StorageRepo<'a, S> {
+    pub fn new(storage: &'a mut S) -> Self {
+        Self { storage }
+    }
+}
+
+impl<'a, S: Storage> ReminderRepo for StorageRepo<'a, S> {
+    fn get_config(&self) -> StdResult<State> {
+        load(self.storage, CONFIG_KEY)
+    }
+
+    fn put_config(&mut self, config: &State) -> StdResult<()> {
+        save(self.storage, CONFIG_KEY, config)
+    }
+
+    fn get_reminder(&self, addr: &CanonicalAddr) -> StdResult<Option<Reminder>> {
+        current_reminder(self.storage, addr)
+    }
+
+    fn put_reminder(&mut self, addr: &CanonicalAddr, content: Vec<u8>, timestamp: u64) -> StdResult<u64> {
+        append_op(self.storage, addr, content, timestamp)
+    }
+
+    fn get_history(&self, addr: &CanonicalAddr, page: u32, page_size: u32) -> StdResult<Vec<Op>> {
+        list_history(self.storage, addr, page, page_size)
+    }
+
+    fn get_viewing_key(&self, addr: &CanonicalAddr) -> Option<Vec<u8>> {
+        read_viewing_key(self.storage, addr)
+    }
+
+    fn put_viewing_key(&mut self, addr: &CanonicalAddr, key: &ViewingKey) {
+        write_viewing_key(self.storage, addr, key)
+    }
+
+    fn get_shared(&self, share_id: &[u8]) -> StdResult<Option<SharedReminder>> {
+        get_shared_body(self.storage, share_id)
+    }
+
+    fn put_shared(&mut self, share_id: &[u8], body: &SharedReminder) -> StdResult<()> {
+        put_shared_body(self.storage, share_id, body)
+    }
+
+    fn get_wrapped_keys(&self, recipient: &CanonicalAddr) -> StdResult<Vec<WrappedKeyEntry>> {
+        get_wrapped_keys(self.storage, recipient)
+    }
+
+    fn put_wrapped_key(&mut self, recipient: &CanonicalAddr, entry: &WrappedKeyEntry) -> StdResult<()> {
+        put_wrapped_key(self.storage, recipient, entry)
+    }
+
+    fn is_permit_revoked(&self, owner: &CanonicalAddr, name: &str) -> bool {
+        is_permit_revoked(self.storage, owner, name)
+    }
+
+    fn revoke_permit(&mut self, owner: &CanonicalAddr, name: &str) -> StdResult<()> {
+        revoke_permit(self.storage, owner, name);
+        Ok(())
+    }
+}
+
+/// A read-only `ReminderRepo` for query handlers, which only ever get a shared `&Storage`
+/// borrow from `Extern`. The `put_*` methods are unreachable since query handlers never call
+/// them; they exist only so this type can satisfy the single `ReminderRepo` interface.
+pub struct ReadonlyStorageRepo<'a, S: ReadonlyStorage> {
+    storage: &'a S,
+}
+
+impl<'a, S: ReadonlyStorage> ReadonlyStorageRepo<'a, S> {
+    pub fn new(storage: &'a S) -> Self {
+        Self { storage }
+    }
+}
+
+impl<'a, S: ReadonlyStorage> ReminderRepo for ReadonlyStorageRepo<'a, S> {
+    fn get_config(&self) -> StdResult<State> {
+        load(self.storage, CONFIG_KEY)
+    }
+
+    fn put_config(&mut self, _config: &State) -> StdResult<()> {
+        unreachable!("query handlers never write to storage")
+    }
+
+    fn get_reminder(&self, addr: &CanonicalAddr) -> StdResult<Option<Reminder>> {
+        current_reminder(self.storage, addr)
+    }
+
+    fn put_reminder(&mut self, _addr: &CanonicalAddr, _content: Vec<u8>, _timestamp: u64) -> StdResult<u64> {
+        unreachable!("query handlers never write to storage")
+    }
+
+    fn get_history(&self, addr: &CanonicalAddr, page: u32, page_size: u32) -> StdResult<Vec<Op>> {
+        list_history(self.storage, addr, page, page_size)
+    }
+
+    fn get_viewing_key(&self, addr: &CanonicalAddr) -> Option<Vec<u8>> {
+        read_viewing_key(self.storage, addr)
+    }
+
+    fn put_viewing_key(&mut self, _addr: &CanonicalAddr, _key: &ViewingKey) {
+        unreachable!("query handlers never write to storage")
+    }
+
+    fn get_shared(&self, share_id: &[u8]) -> StdResult<Option<SharedReminder>> {
+        get_shared_body(self.storage, share_id)
+    }
+
+    fn put_shared(&mut self, _share_id: &[u8], _body: &SharedReminder) -> StdResult<()> {
+        unreachable!("query handlers never write to storage")
+    }
+
+    fn get_wrapped_keys(&self, recipient: &CanonicalAddr) -> StdResult<Vec<WrappedKeyEntry>> {
+        get_wrapped_keys(self.storage, recipient)
+    }
+
+    fn put_wrapped_key(&mut self, _recipient: &CanonicalAddr, _entry: &WrappedKeyEntry) -> StdResult<()> {
+        unreachable!("query handlers never write to storage")
+    }
+
+    fn is_permit_revoked(&self, owner: &CanonicalAddr, name: &str) -> bool {
+        is_permit_revoked(self.storage, owner, name)
+    }
+
+    fn revoke_permit(&mut self, _owner: &CanonicalAddr, _name: &str) -> StdResult<()> {
+        unreachable!("query handlers never write to storage")
+    }
+}
+
+/// A `ReminderRepo` backed by plain Rust collections instead of CosmWasm storage, for unit
+/// tests and fuzzing where spinning up a full `Extern` isn't worth it. Keeps the full history
+/// per address in memory rather than checkpointing, since replay cost isn't a concern here.
+#[derive(Default)]
+pub struct InMemoryRepo {
+    config: Option<State>,
+    ops: HashMap<Vec<u8>, Vec<Op>>,
+    viewing_keys: HashMap<Vec<u8>, Vec<u8>>,
+    shared_bodies: HashMap<Vec<u8>, SharedReminder>,
+    wrapped_keys: HashMap<Vec<u8>, Vec<WrappedKeyEntry>>,
+    revoked_permits: HashMap<Vec<u8>, Vec<String>>,
+}
+
+impl InMemoryRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ReminderRepo for InMemoryRepo {
+    fn get_config(&self) -> StdResult<State> {
+        self.config
+            .clone()
+            .ok_or_else(|| StdError::not_found("State"))
+    }
+
+    fn put_config(&mut self, config: &State) -> StdResult<()> {
+        self.config = Some(config.clone());
+        Ok(())
+    }
+
+    fn get_reminder(&self, addr: &CanonicalAddr) -> StdResult<Option<Reminder>> {
+        Ok(self
+            .ops
+            .get(addr.as_slice())
+            .and_then(|ops| ops.last())
+            .map(|op| Reminder { content: op.content.clone(), timestamp: op.timestamp }))
+    }
+
+    fn put_reminder(&mut self, addr: &CanonicalAddr, content: Vec<u8>, timestamp: u64) -> StdResult<u64> {
+        let history = self.ops.entry(addr.as_slice().to_vec()).or_default();
+        let seq = history.last().map(|op| op.seq).unwrap_or(0) + 1;
+        history.push(Op { seq, content, timestamp });
+        Ok(seq)
+    }
+
+    fn get_history(&self, addr: &CanonicalAddr, page: u32, page_size: u32) -> StdResult<Vec<Op>> {
+        let history = self.ops.get(addr.as_slice()).cloned().unwrap_or_default();
+        let skip = (page as usize) * (page_size as usize);
+        Ok(history.into_iter().rev().skip(skip).take(page_size as usize).collect())
+    }
+
+    fn get_viewing_key(&self, addr: &CanonicalAddr) -> Option<Vec<u8>> {
+        self.viewing_keys.get(addr.as_slice()).cloned()
+    }
+
+    fn put_viewing_key(&mut self, addr: &CanonicalAddr, key: &ViewingKey) {
+        self.viewing_keys.insert(addr.as_slice().to_vec(), key.to_hashed());
+    }
+
+    fn get_shared(&self, share_id: &[u8]) -> StdResult<Option<SharedReminder>> {
+        Ok(self.shared_bodies.get(share_id).cloned())
+    }
+
+    fn put_shared(&mut self, share_id: &[u8], body: &SharedReminder) -> StdResult<()> {
+        self.shared_bodies.insert(share_id.to_vec(), body.clone());
+        Ok(())
+    }
+
+    fn get_wrapped_keys(&self, recipient: &CanonicalAddr) -> StdResult<Vec<WrappedKeyEntry>> {
+        Ok(self.wrapped_keys.get(recipient.as_slice()).cloned().unwrap_or_default())
+    }
+
+    fn put_wrapped_key(&mut self, recipient: &CanonicalAddr, entry: &WrappedKeyEntry) -> StdResult<()> {
+        let entries = self.wrapped_keys.entry(recipient.as_slice().to_vec()).or_default();
+        if !entries.iter().any(|e| e.share_id == entry.share_id) {
+            entries.push(entry.clone());
+        }
+        Ok(())
+    }
+
+    fn is_permit_revoked(&self, owner: &CanonicalAddr, name: &str) -> bool {
+        self.revoked_permits
+            .get(owner.as_slice())
+            .map(|names| names.iter().any(|n| n == name))
+            .unwrap_or(false)
+    }
+
+    fn revoke_permit(&mut self, owner: &CanonicalAddr, name: &str) -> StdResult<()> {
+        self.revoked_permits
+            .entry(owner.as_slice().to_vec())
+            .or_default()
+            .push(name.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::Binary;
+
+    fn addr(byte: u8) -> CanonicalAddr {
+        CanonicalAddr(Binary(vec![byte; 20]))
+    }
+
+    fn sample_config() -> State {
+        State {
+            max_size: 1024,
+            reminder_count: 0,
+            prng_seed: vec![7; 32],
+            admin: addr(0xA0),
+            paused: false,
+            contract: addr(0xC0),
+        }
+    }
+
+    #[test]
+    fn in_memory_record_and_read_roundtrip() {
+        let mut repo = InMemoryRepo::new();
+        repo.put_config(&sample_config()).unwrap();
+
+        let who = addr(1);
+        assert_eq!(repo.get_reminder(&who).unwrap(), None);
+
+        repo.put_reminder(&who, b"buy milk".to_vec(), 100).unwrap();
+        let reminder = repo.get_reminder(&who).unwrap().unwrap();
+        assert_eq!(reminder.content, b"buy milk");
+        assert_eq!(reminder.timestamp, 100);
+
+        // a later edit replaces the current value but keeps the earlier one in history
+        repo.put_reminder(&who, b"buy oat milk".to_vec(), 200).unwrap();
+        let reminder = repo.get_reminder(&who).unwrap().unwrap();
+        assert_eq!(reminder.content, b"buy oat milk");
+    }
+
+    #[test]
+    fn in_memory_history_is_most_recent_first_and_paginates() {
+        let mut repo = InMemoryRepo::new();
+        let who = addr(2);
+        for (content, timestamp) in [("a", 1), ("b", 2), ("c", 3)] {
+            repo.put_reminder(&who, content.as_bytes().to_vec(), timestamp).unwrap();
+        }
+
+        let page0 = repo.get_history(&who, 0, 2).unwrap();
+        assert_eq!(page0.iter().map(|op| op.content.clone()).collect::<Vec<_>>(), vec![b"c".to_vec(), b"b".to_vec()]);
+
+        let page1 = repo.get_history(&who, 1, 2).unwrap();
+        assert_eq!(page1.iter().map(|op| op.content.clone()).collect::<Vec<_>>(), vec![b"a".to_vec()]);
+    }
+
+    #[test]
+    fn in_memory_share_and_wrapped_key_roundtrip() {
+        let mut repo = InMemoryRepo::new();
+        let author = addr(3);
+        let recipient = addr(4);
+
+        let shared = SharedReminder {
+            author: author.clone(),
+            ciphertext: vec![9, 9, 9],
+            nonce: vec![1, 2, 3],
+            timestamp: 42,
+        };
+        repo.put_shared(b"share-1", &shared).unwrap();
+        assert_eq!(repo.get_shared(b"share-1").unwrap(), Some(shared));
+
+        let entry = WrappedKeyEntry {
+            share_id: b"share-1".to_vec(),
+            wrapped_key: vec![0; 32],
+            vk_hash: vec![1; 32],
+        };
+        repo.put_wrapped_key(&recipient, &entry).unwrap();
+        assert_eq!(repo.get_wrapped_keys(&recipient).unwrap(), vec![entry]);
+    }
+
+    #[test]
+    fn in_memory_revoked_permits_are_scoped_per_owner() {
+        let mut repo = InMemoryRepo::new();
+        let owner = addr(5);
+        let other = addr(6);
+
+        assert!(!repo.is_permit_revoked(&owner, "main"));
+        repo.revoke_permit(&owner, "main").unwrap();
+        assert!(repo.is_permit_revoked(&owner, "main"));
+        assert!(!repo.is_permit_revoked(&owner, "other-permit"));
+        assert!(!repo.is_permit_revoked(&other, "main"));
+    }
+
+    /// Drives the same sequence of `ReminderRepo` operations through `InMemoryRepo` and through
+    /// `StorageRepo` backed by `MockStorage`, checking that the current reminder, history,
+    /// viewing key and permit revocation reads agree between the two. Config and shared/wrapped
+    /// key reads are not compared here.
+    #[test]
+    fn in_memory_repo_matches_storage_repo() {
+        let mut mock_storage = MockStorage::new();
+        let mut storage_repo = StorageRepo::new(&mut mock_storage);
+        let mut memory_repo = InMemoryRepo::new();
+
+        let config = sample_config();
+        storage_repo.put_config(&config).unwrap();
+        memory_repo.put_config(&config).unwrap();
+
+        let who = addr(9);
+        for (content, timestamp) in [("first", 10), ("second", 20), ("third", 30)] {
+            storage_repo.put_reminder(&who, content.as_bytes().to_vec(), timestamp).unwrap();
+            memory_repo.put_reminder(&who, content.as_bytes().to_vec(), timestamp).unwrap();
+        }
+
+        assert_eq!(storage_repo.get_reminder(&who).unwrap(), memory_repo.get_reminder(&who).unwrap());
+
+        let storage_history = storage_repo.get_history(&who, 0, 10).unwrap();
+        let memory_history = memory_repo.get_history(&who, 0, 10).unwrap();
+        assert_eq!(
+            storage_history.iter().map(|op| (op.content.clone(), op.timestamp)).collect::<Vec<_>>(),
+            memory_history.iter().map(|op| (op.content.clone(), op.timestamp)).collect::<Vec<_>>(),
+        );
+
+        let vk = ViewingKey("api_key_test".to_string());
+        storage_repo.put_viewing_key(&who, &vk);
+        memory_repo.put_viewing_key(&who, &vk);
+        assert_eq!(storage_repo.get_viewing_key(&who), memory_repo.get_viewing_key(&who));
+
+        storage_repo.revoke_permit(&who, "leaked").unwrap();
+        memory_repo.revoke_permit(&who, "leaked").unwrap();
+        assert_eq!(storage_repo.is_permit_revoked(&who, "leaked"), memory_repo.is_permit_revoked(&who, "leaked"));
+        assert_eq!(storage_repo.is_permit_revoked(&who, "other"), memory_repo.is_permit_revoked(&who, "other"));
+    }
+}
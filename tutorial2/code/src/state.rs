@@ -2,12 +2,26 @@ use std::{any::type_name};
 use serde::{Deserialize, Serialize};
 use cosmwasm_std::{Storage, ReadonlyStorage, StdResult, StdError, CanonicalAddr,};
 use serde::de::DeserializeOwned;
-use secret_toolkit::serialization::{Bincode2, Serde,};
 use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use crate::codec::{self, BincodeCodec, CompressedCodec, JsonCodec, Codec};
 use crate::viewing_key::ViewingKey;
 
 pub static CONFIG_KEY: &[u8] = b"config";
 pub const PREFIX_VIEWING_KEY: &[u8] = b"viewingkey";
+pub const PREFIX_OPS: &[u8] = b"ops";
+pub const PREFIX_CKPT: &[u8] = b"ckpt";
+pub const PREFIX_SHARED_BODY: &[u8] = b"shared-body";
+pub const PREFIX_SHARED_WRAPPED: &[u8] = b"shared-wrapped";
+pub const PREFIX_REVOKED_PERMITS: &[u8] = b"revoked-permits";
+
+/// Number of operations between materialized checkpoints. Larger values save storage writes
+/// on `Record` but make replay on `Read` more expensive. `History` does not replay; it reads
+/// the stored ops directly.
+pub const CHECKPOINT_INTERVAL: u64 = 64;
+
+/// Number of operations to keep on disk behind the latest checkpoint. Ops older than this are
+/// pruned once a new checkpoint makes them unnecessary for reconstructing the current value.
+pub const OP_RETENTION: u64 = 256;
 
 pub fn write_viewing_key<S: Storage>(store: &mut S, owner: &CanonicalAddr, key: &ViewingKey) {
     let mut user_key_store = PrefixedStorage::new(PREFIX_VIEWING_KEY, store);
@@ -19,11 +33,29 @@ pub fn read_viewing_key<S: Storage>(store: &S, owner: &CanonicalAddr) -> Option<
     user_key_store.get(owner.as_slice())
 }
 
+/// Marks `name` as revoked for `owner`, so a permit signed under that name is rejected even if
+/// it is still otherwise validly signed. Stored as set membership (presence of the key, not its
+/// value) rather than a list, since the only operations needed are "add" and "is this in there".
+pub fn revoke_permit<S: Storage>(store: &mut S, owner: &CanonicalAddr, name: &str) {
+    let mut revoked_store = PrefixedStorage::new(PREFIX_REVOKED_PERMITS, store);
+    revoked_store.set(&[owner.as_slice(), name.as_bytes()].concat(), &[1]);
+}
+
+pub fn is_permit_revoked<S: ReadonlyStorage>(store: &S, owner: &CanonicalAddr, name: &str) -> bool {
+    let revoked_store = ReadonlyPrefixedStorage::new(PREFIX_REVOKED_PERMITS, store);
+    revoked_store.get(&[owner.as_slice(), name.as_bytes()].concat()).is_some()
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct State {
     pub max_size: u16,
     pub reminder_count: u64,
     pub prng_seed: Vec<u8>,
+    pub admin: CanonicalAddr,
+    pub paused: bool,
+    /// This contract's own address, recorded at `init` so a signed [`crate::msg::Permit`] can
+    /// be checked against it and rejected if it was scoped to a different contract.
+    pub contract: CanonicalAddr,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -32,13 +64,243 @@ pub struct Reminder {
     pub timestamp: u64,
 }
 
+/// A single immutable edit to a user's reminder, identified by a monotonically increasing
+/// sequence number. The current value is whichever op has the highest `seq`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Op {
+    pub seq: u64,
+    pub content: Vec<u8>,
+    pub timestamp: u64,
+}
+
+/// A materialized snapshot of a user's reminder as of `seq`, so `Read` doesn't have to replay
+/// the full operation log from the beginning every time.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub seq: u64,
+    pub reminder: Reminder,
+}
+
+fn op_key(addr: &CanonicalAddr, seq: u64) -> Vec<u8> {
+    let mut key = addr.as_slice().to_vec();
+    key.extend_from_slice(&seq.to_be_bytes());
+    key
+}
+
+fn counter_key(addr: &CanonicalAddr) -> Vec<u8> {
+    addr.as_slice().to_vec()
+}
+
+/// Appends a new operation to `addr`'s history, advancing its sequence counter, and returns
+/// the newly assigned sequence number. Every `CHECKPOINT_INTERVAL` operations, a checkpoint of
+/// the resulting state is materialized and operations older than `OP_RETENTION` are pruned.
+pub fn append_op<S: Storage>(
+    storage: &mut S,
+    addr: &CanonicalAddr,
+    content: Vec<u8>,
+    timestamp: u64,
+) -> StdResult<u64> {
+    let seq = next_seq(storage, addr)?;
+    let op = Op { seq, content, timestamp };
+
+    let mut op_store = PrefixedStorage::new(PREFIX_OPS, storage);
+    save_with::<CompressedCodec, _, _>(&mut op_store, &op_key(addr, seq), &op)?;
+    drop(op_store);
+
+    if seq % CHECKPOINT_INTERVAL == 0 {
+        let mut ckpt_store = PrefixedStorage::new(PREFIX_CKPT, storage);
+        // stored as JSON rather than the default bincode so a checkpoint can be inspected
+        // directly with a raw storage query, without needing a bincode decoder on hand
+        save_with::<JsonCodec, _, _>(&mut ckpt_store, counter_key(addr).as_slice(), &Checkpoint {
+            seq,
+            reminder: Reminder { content: op.content.clone(), timestamp: op.timestamp },
+        })?;
+        drop(ckpt_store);
+
+        if seq > OP_RETENTION {
+            prune_ops(storage, addr, seq - OP_RETENTION)?;
+        }
+    }
+
+    Ok(seq)
+}
+
+/// Loads and increments the per-user operation counter, a `u64` saved with [`save`] under the
+/// `PREFIX_OPS` store at `addr || "_seq"`.
+fn next_seq<S: Storage>(storage: &mut S, addr: &CanonicalAddr) -> StdResult<u64> {
+    let mut counter_store = PrefixedStorage::new(PREFIX_OPS, storage);
+    let key = counter_key(addr);
+    let last: u64 = may_load(&counter_store, &[key.as_slice(), b"_seq"].concat())?.unwrap_or(0);
+    let seq = last + 1;
+    save(&mut counter_store, &[key.as_slice(), b"_seq"].concat(), &seq)?;
+    Ok(seq)
+}
+
+fn prune_watermark_key(addr: &CanonicalAddr) -> Vec<u8> {
+    [counter_key(addr).as_slice(), b"_pruned"].concat()
+}
+
+/// Deletes ops for `addr` with a sequence number below `retain_from_seq`. Anything at or above
+/// that point is still needed to replay forward from the latest checkpoint.
+///
+/// Only deletes the window above the persisted watermark (the `retain_from_seq` passed in on
+/// the previous call), rather than rescanning from seq 1 every time: without the watermark, an
+/// active user re-issues a `remove` for every op ever pruned on each new checkpoint, which is
+/// unbounded work per checkpoint instead of the `CHECKPOINT_INTERVAL`-sized window that's
+/// actually new.
+fn prune_ops<S: Storage>(storage: &mut S, addr: &CanonicalAddr, retain_from_seq: u64) -> StdResult<()> {
+    let mut op_store = PrefixedStorage::new(PREFIX_OPS, storage);
+    let watermark_key = prune_watermark_key(addr);
+    let pruned_through: u64 = may_load(&op_store, &watermark_key)?.unwrap_or(1);
+
+    for seq in pruned_through..retain_from_seq {
+        op_store.remove(&op_key(addr, seq));
+    }
+
+    save(&mut op_store, &watermark_key, &retain_from_seq)
+}
+
+/// Reconstructs the current reminder for `addr` by loading the latest checkpoint (if any) and
+/// replaying only the ops with a higher sequence number, bounding replay cost to at most
+/// `CHECKPOINT_INTERVAL` ops.
+pub fn current_reminder<S: ReadonlyStorage>(storage: &S, addr: &CanonicalAddr) -> StdResult<Option<Reminder>> {
+    let ckpt_store = ReadonlyPrefixedStorage::new(PREFIX_CKPT, storage);
+    let checkpoint: Option<Checkpoint> = may_load(&ckpt_store, &counter_key(addr))?;
+
+    let counter_store = ReadonlyPrefixedStorage::new(PREFIX_OPS, storage);
+    let last_seq: u64 = may_load(&counter_store, &[counter_key(addr).as_slice(), b"_seq"].concat())?.unwrap_or(0);
+
+    let (mut reminder, from_seq) = match checkpoint {
+        Some(c) => (Some(c.reminder), c.seq + 1),
+        None => (None, 1),
+    };
+
+    let op_store = ReadonlyPrefixedStorage::new(PREFIX_OPS, storage);
+    for seq in from_seq..=last_seq {
+        if let Some(op) = may_load::<Op, _>(&op_store, &op_key(addr, seq))? {
+            reminder = Some(Reminder { content: op.content, timestamp: op.timestamp });
+        }
+    }
+
+    Ok(reminder)
+}
+
+/// Returns one page of `addr`'s history, most recent entry first.
+pub fn list_history<S: ReadonlyStorage>(
+    storage: &S,
+    addr: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Vec<Op>> {
+    let counter_store = ReadonlyPrefixedStorage::new(PREFIX_OPS, storage);
+    let last_seq: u64 = may_load(&counter_store, &[counter_key(addr).as_slice(), b"_seq"].concat())?.unwrap_or(0);
+
+    let op_store = ReadonlyPrefixedStorage::new(PREFIX_OPS, storage);
+    let skip = (page as u64) * (page_size as u64);
+    let mut entries = Vec::with_capacity(page_size as usize);
+    let mut seen = 0u64;
+    let mut seq = last_seq;
+    while seq >= 1 && entries.len() < page_size as usize {
+        if let Some(op) = may_load::<Op, _>(&op_store, &op_key(addr, seq))? {
+            if seen >= skip {
+                entries.push(op);
+            }
+            seen += 1;
+        }
+        seq -= 1;
+    }
+
+    Ok(entries)
+}
+
+/// The encrypted body of a shared reminder, stored once under a random `share_id` no matter
+/// how many recipients it was shared with.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SharedReminder {
+    pub author: CanonicalAddr,
+    pub ciphertext: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub timestamp: u64,
+}
+
+/// A recipient's wrapped copy of a shared reminder's content key, pointing at the `share_id` of
+/// the body it unlocks. Keyed by `(recipient, share_id)` rather than by recipient alone, so a
+/// recipient can hold a wrapped key for every share they've received rather than just the most
+/// recent one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WrappedKeyEntry {
+    pub share_id: Vec<u8>,
+    pub wrapped_key: Vec<u8>,
+    /// Hash of the recipient's viewing key at the time this entry was wrapped. A later
+    /// `GenerateViewingKey` call rotates that hash, which would otherwise derive the wrong
+    /// wrapping key silently; comparing against this lets a reader detect the rotation and
+    /// treat the entry as stale instead.
+    pub vk_hash: Vec<u8>,
+}
+
+pub fn put_shared_body<S: Storage>(storage: &mut S, share_id: &[u8], body: &SharedReminder) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(PREFIX_SHARED_BODY, storage);
+    save_with::<CompressedCodec, _, _>(&mut store, share_id, body)
+}
+
+pub fn get_shared_body<S: ReadonlyStorage>(storage: &S, share_id: &[u8]) -> StdResult<Option<SharedReminder>> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_SHARED_BODY, storage);
+    may_load(&store, share_id)
+}
+
+fn wrapped_key_key(recipient: &CanonicalAddr, share_id: &[u8]) -> Vec<u8> {
+    [recipient.as_slice(), share_id].concat()
+}
+
+fn wrapped_key_index_key(recipient: &CanonicalAddr) -> Vec<u8> {
+    recipient.as_slice().to_vec()
+}
+
+/// Stores `entry` for `recipient`, keyed by `(recipient, entry.share_id)`, and records its
+/// `share_id` in `recipient`'s index so it can later be enumerated: plain CosmWasm storage has
+/// no range/iteration primitive to discover `(recipient, *)` keys directly.
+pub fn put_wrapped_key<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, entry: &WrappedKeyEntry) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(PREFIX_SHARED_WRAPPED, storage);
+    save(&mut store, &wrapped_key_key(recipient, &entry.share_id), entry)?;
+    drop(store);
+
+    let mut index_store = PrefixedStorage::new(PREFIX_SHARED_WRAPPED, storage);
+    let index_key = wrapped_key_index_key(recipient);
+    let mut share_ids: Vec<Vec<u8>> = may_load(&index_store, &index_key)?.unwrap_or_default();
+    if !share_ids.iter().any(|id| id == &entry.share_id) {
+        share_ids.push(entry.share_id.clone());
+    }
+    // a small, human-inspectable list rather than the default bincode encoding
+    save_with::<JsonCodec, _, _>(&mut index_store, &index_key, &share_ids)
+}
+
+/// Returns every wrapped key entry `recipient` has received, in the order they were shared.
+pub fn get_wrapped_keys<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr) -> StdResult<Vec<WrappedKeyEntry>> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_SHARED_WRAPPED, storage);
+    let share_ids: Vec<Vec<u8>> = may_load(&store, &wrapped_key_index_key(recipient))?.unwrap_or_default();
+
+    share_ids
+        .into_iter()
+        .filter_map(|share_id| may_load::<WrappedKeyEntry, _>(&store, &wrapped_key_key(recipient, &share_id)).transpose())
+        .collect()
+}
+
+/// Saves `value` using the default (bincode) on-disk format. Kept for backward compatibility;
+/// use [`save_with`] to pick a different [`Codec`], e.g. [`CompressedCodec`] for large values.
 pub fn save<T: Serialize, S: Storage>(storage: &mut S, key: &[u8], value: &T) -> StdResult<()> {
-    storage.set(key, &Bincode2::serialize(value)?);
+    save_with::<BincodeCodec, T, S>(storage, key, value)
+}
+
+/// Saves `value` encoded with codec `C`, prefixed with a one-byte format tag identifying that
+/// encoding. Reads are tag-driven, so values written under different codecs over time can
+/// coexist in storage and are decoded correctly without needing to know which codec wrote them.
+pub fn save_with<C: Codec, T: Serialize, S: Storage>(storage: &mut S, key: &[u8], value: &T) -> StdResult<()> {
+    storage.set(key, &codec::encode::<T, C>(value)?);
     Ok(())
 }
 
 pub fn load<T: DeserializeOwned, S: ReadonlyStorage>(storage: &S, key: &[u8]) -> StdResult<T> {
-    Bincode2::deserialize(
+    codec::decode(
         &storage
             .get(key)
             .ok_or_else(|| StdError::not_found(type_name::<T>()))?,
@@ -47,7 +309,7 @@ pub fn load<T: DeserializeOwned, S: ReadonlyStorage>(storage: &S, key: &[u8]) ->
 
 pub fn may_load<T: DeserializeOwned, S: ReadonlyStorage>(storage: &S, key: &[u8]) -> StdResult<Option<T>> {
     match storage.get(key) {
-        Some(value) => Bincode2::deserialize(&value).map(Some),
+        Some(value) => codec::decode(&value).map(Some),
         None => Ok(None),
     }
 }
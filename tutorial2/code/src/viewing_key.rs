@@ -0,0 +1,55 @@
+use std::fmt;
+
+use cosmwasm_std::Env;
+use secret_toolkit::crypto::sha_256;
+use serde::{Deserialize, Serialize};
+
+pub const VIEWING_KEY_SIZE: usize = 32;
+pub const VIEWING_KEY_PREFIX: &str = "api_key_";
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, schemars::JsonSchema)]
+pub struct ViewingKey(pub String);
+
+impl ViewingKey {
+    /// Derives a new viewing key from the contract's prng seed, the caller's entropy, and
+    /// details of the current block so that two calls never produce the same key.
+    pub fn new(env: &Env, seed: &[u8], entropy: &[u8]) -> Self {
+        let mut key_material = seed.to_vec();
+        key_material.extend_from_slice(&env.block.height.to_be_bytes());
+        key_material.extend_from_slice(&env.block.time.to_be_bytes());
+        key_material.extend_from_slice(env.message.sender.0.as_bytes());
+        key_material.extend_from_slice(entropy);
+
+        Self(VIEWING_KEY_PREFIX.to_string() + &base64::encode(sha_256(&key_material)))
+    }
+
+    /// Returns the sha256 hash of the key, which is what gets stored so the plaintext key
+    /// never touches contract storage.
+    pub fn to_hashed(&self) -> Vec<u8> {
+        sha_256(self.0.as_bytes()).to_vec()
+    }
+
+    pub fn check_viewing_key(&self, hashed_key: &[u8]) -> bool {
+        ct_slice_eq(&self.to_hashed(), hashed_key)
+    }
+}
+
+/// Compares two byte slices without branching on where they first differ, so checking a stored
+/// viewing key hash against a supplied one can't leak timing information about how many bytes
+/// matched. Complements the dummy-key check `authenticated_queries` does when no key is set.
+fn ct_slice_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
+
+impl fmt::Display for ViewingKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}